@@ -0,0 +1,140 @@
+//! Epoch-based reclamation (the `crossbeam`/`coco` idea) so a deferred
+//! free can't race a reader that still holds a reference.
+
+use libc::{size_t, uint32_t};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static GLOBAL_EPOCH: AtomicUsize = AtomicUsize::new(0);
+static NEXT_GUARD_ID: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// Epoch each currently-live guard last observed, keyed by a unique
+    /// per-guard id rather than `ThreadId` -- a thread may hold more than
+    /// one guard at once (e.g. reading two handles at the same time), and
+    /// keying on thread alone would let the second `pin()` clobber the
+    /// first guard's entry.
+    static ref PINNED: Mutex<HashMap<u64, usize>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+    static GARBAGE: RefCell<Vec<(usize, Box<dyn Send>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// RAII guard returned by `pin()`. Unpins its own entry on drop.
+pub struct Guard {
+    id: u64,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        PINNED.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Publish the current global epoch as "observed" by this guard. Hold
+/// the returned `Guard` for as long as any FFI-owned reference is in
+/// use; dropping it unpins again. Safe to nest -- each `pin()` call gets
+/// its own entry, so multiple live guards (same thread or not) don't
+/// interfere with each other.
+pub fn pin() -> Guard {
+    let epoch = GLOBAL_EPOCH.load(Ordering::SeqCst);
+    let id = NEXT_GUARD_ID.fetch_add(1, Ordering::Relaxed);
+    PINNED.lock().unwrap().insert(id, epoch);
+    Guard { id }
+}
+
+/// Advance the global epoch if no pinned thread is still observing an
+/// older one, returning the epoch that is now safe to reclaim up to.
+fn try_advance() -> usize {
+    let pinned = PINNED.lock().unwrap();
+    let global = GLOBAL_EPOCH.load(Ordering::SeqCst);
+    match pinned.values().cloned().min() {
+        Some(oldest) if oldest < global => oldest,
+        _ => {
+            let next = global + 1;
+            GLOBAL_EPOCH.store(next, Ordering::SeqCst);
+            next
+        }
+    }
+}
+
+/// Retire `item`, to be dropped once no thread pinned at or before the
+/// current epoch could still be observing it.
+pub fn defer<T: Send + 'static>(item: T) {
+    let epoch = GLOBAL_EPOCH.load(Ordering::SeqCst);
+    GARBAGE.with(|bag| bag.borrow_mut().push((epoch, Box::new(item) as Box<dyn Send>)));
+    collect();
+}
+
+/// Drop any of this thread's garbage retired at least two epochs before
+/// the epoch we can currently prove safe.
+fn collect() {
+    let safe_epoch = try_advance();
+    GARBAGE.with(|bag| {
+        bag.borrow_mut().retain(|&(retired_at, _)| retired_at + 2 > safe_epoch);
+    });
+}
+
+#[no_mangle]
+/// FFI-safe alternative to `drop_vec`: defers the free via `defer`
+/// instead of deallocating `data_ptr` immediately.
+///
+/// # Safety
+/// `data_ptr`/`len`/`cap` must be a `Vec<u32>`'s raw parts, not already freed.
+pub unsafe extern "C" fn defer_drop_vec(data_ptr: *mut uint32_t, len: size_t, cap: size_t) {
+    assert!(!data_ptr.is_null());
+    let vec = Vec::from_raw_parts(data_ptr, len, cap);
+    defer(vec);
+}
+
+#[no_mangle]
+/// Pin the calling thread and hand back an opaque guard. Call this before
+/// reading a pointer that a concurrent `vec_free`/`defer_drop_vec` might
+/// otherwise reclaim, and release the guard with `vec_unpin` once done
+/// reading -- without this, nothing protects a `vec_get_ptr` result.
+pub extern "C" fn vec_pin() -> *mut Guard {
+    Box::into_raw(Box::new(pin()))
+}
+
+#[no_mangle]
+/// Release a guard returned by `vec_pin`, unpinning the calling thread.
+///
+/// # Safety
+/// `guard` must be a pointer returned by `vec_pin`, not already unpinned.
+pub unsafe extern "C" fn vec_unpin(guard: *mut Guard) {
+    if !guard.is_null() {
+        drop(Box::from_raw(guard));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize as DropCount;
+    use std::sync::Arc;
+
+    struct MarkOnDrop(Arc<DropCount>);
+    impl Drop for MarkOnDrop {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn garbage_retained_while_pinned_then_reclaimed_after_unpin() {
+        let dropped = Arc::new(DropCount::new(0));
+        let guard = pin();
+        defer(MarkOnDrop(dropped.clone()));
+        collect();
+        assert_eq!(dropped.load(Ordering::SeqCst), 0, "still pinned, must not be reclaimed yet");
+
+        drop(guard);
+        for _ in 0..4 {
+            collect();
+        }
+        assert_eq!(dropped.load(Ordering::SeqCst), 1, "unpinned, should be reclaimed");
+    }
+}
@@ -0,0 +1,140 @@
+//! Iterative Fibonacci (`fib_fast`), an optional process-global memo
+//! cache shared across callers (`fib_cached`), and a runtime toggle
+//! (`set_fib_mode`) selecting which implementation the `fib_*` FFI
+//! entry points dispatch to -- `fib_slow`, the original exponential
+//! naive-recursive version, is kept only for demonstrating the
+//! difference.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use super::fib_slow;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// Which implementation `fib()` dispatches to.
+pub enum FibMode {
+    /// Exponential, uncached -- `fib_slow`. For demonstration/benchmarking
+    /// against the fast path only; don't use this for real batches.
+    NaiveRecursive = 0,
+    /// O(n) iterative, sharing a process-global memo cache across calls
+    /// -- including `fib_many`'s workers, so a repeated `n` within a
+    /// batch is only computed once. The default.
+    IterativeCached = 1,
+}
+
+static MODE: AtomicUsize = AtomicUsize::new(FibMode::IterativeCached as usize);
+
+#[cfg(feature = "std")]
+lazy_static! {
+    static ref CACHE: Mutex<HashMap<u32, u32>> = Mutex::new(HashMap::new());
+}
+
+#[no_mangle]
+/// Select the implementation subsequent `fib_*` FFI calls dispatch to
+/// (see `FibMode`). Unrecognized values are treated as `IterativeCached`.
+pub extern "C" fn set_fib_mode(mode: u32) {
+    let mode = if mode == FibMode::NaiveRecursive as u32 {
+        FibMode::NaiveRecursive
+    } else {
+        FibMode::IterativeCached
+    };
+    MODE.store(mode as usize, Ordering::SeqCst);
+}
+
+fn mode() -> FibMode {
+    if MODE.load(Ordering::SeqCst) == FibMode::NaiveRecursive as usize {
+        FibMode::NaiveRecursive
+    } else {
+        FibMode::IterativeCached
+    }
+}
+
+/// Dispatch to the implementation currently selected by `set_fib_mode`.
+/// This is what every `fib_*` FFI entry point should call, rather than
+/// `fib_slow` directly, so the mode toggle actually takes effect.
+pub fn fib(n: u32) -> u32 {
+    match mode() {
+        FibMode::NaiveRecursive => fib_slow(n),
+        FibMode::IterativeCached => fib_cached(n),
+    }
+}
+
+/// O(n) iterative Fibonacci, matching `fib_slow`'s indexing
+/// (`fib_fast(0) == 0`, `fib_fast(1) == 1`, ...). Saturates at
+/// `u32::MAX` rather than silently wrapping once `n` is large enough
+/// that `fib(n)` no longer fits in a `u32` (from `n == 48` on).
+pub fn fib_fast(n: u32) -> u32 {
+    if n == 0 {
+        return 0;
+    }
+    let mut a: u32 = 0;
+    let mut b: u32 = 1;
+    for _ in 1..n {
+        let next = a.saturating_add(b);
+        a = b;
+        b = next;
+    }
+    b
+}
+
+#[cfg(feature = "std")]
+/// `fib_fast`, memoized in a process-global cache shared by every
+/// caller.
+pub fn fib_cached(n: u32) -> u32 {
+    if let Some(&cached) = CACHE.lock().unwrap().get(&n) {
+        return cached;
+    }
+    let result = fib_fast(n);
+    CACHE.lock().unwrap().insert(n, result);
+    result
+}
+
+#[cfg(not(feature = "std"))]
+/// No process-global cache without `std`'s `Mutex`/`HashMap` -- just
+/// the iterative fast path.
+pub fn fib_cached(n: u32) -> u32 {
+    fib_fast(n)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fib_fast_matches_naive_recursion() {
+        for n in 0..20 {
+            assert_eq!(fib_fast(n), fib_slow(n));
+        }
+    }
+
+    #[test]
+    fn fib_fast_saturates_instead_of_overflowing() {
+        assert_eq!(fib_fast(47), 2971215073);
+        assert_eq!(fib_fast(48), u32::MAX);
+        assert_eq!(fib_fast(100), u32::MAX);
+    }
+
+    #[test]
+    fn fib_cached_matches_fib_fast() {
+        for n in 0..20 {
+            assert_eq!(fib_cached(n), fib_fast(n));
+        }
+    }
+
+    #[test]
+    fn set_fib_mode_switches_dispatch() {
+        set_fib_mode(FibMode::NaiveRecursive as u32);
+        assert_eq!(fib(10), fib_slow(10));
+
+        set_fib_mode(FibMode::IterativeCached as u32);
+        assert_eq!(fib(10), fib_fast(10));
+
+        // Unrecognized values fall back to IterativeCached.
+        set_fib_mode(99);
+        assert_eq!(fib(10), fib_fast(10));
+    }
+}
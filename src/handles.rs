@@ -0,0 +1,86 @@
+//! Opaque-handle registry for FFI-owned result vectors, so the FFI
+//! boundary passes a `u64` token instead of a raw `data`/`len`/`cap`
+//! triple.
+
+use libc::{size_t, uint32_t};
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use super::epoch;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<HashMap<u64, Vec<u32>>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+#[repr(C)]
+/// A result vector identified by an opaque handle rather than a raw
+/// pointer. Use `vec_len`/`vec_get_ptr` to read it and `vec_free` to
+/// release it once done. Bracket any read with `epoch::vec_pin`/
+/// `vec_unpin` if a concurrent `vec_free` is possible, or the pointer
+/// can be reclaimed while you're still holding it.
+pub struct VectorHandle {
+    pub handle: u64,
+    pub len: size_t,
+}
+
+/// Hand ownership of `vec` to the registry, returning the handle an FFI
+/// caller can use to reference it.
+pub fn register(vec: Vec<u32>) -> VectorHandle {
+    let len = vec.len();
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    REGISTRY.lock().unwrap().insert(handle, vec);
+    VectorHandle { handle, len }
+}
+
+#[no_mangle]
+/// Allocate a zeroed `length`-element vector and register it, returning
+/// its handle.
+pub extern "C" fn vec_create(length: size_t) -> VectorHandle {
+    register(vec![0u32; length])
+}
+
+#[no_mangle]
+/// Number of elements in the vector behind `handle`, or 0 if unknown.
+pub extern "C" fn vec_len(handle: u64) -> size_t {
+    REGISTRY.lock().unwrap().get(&handle).map_or(0, |v| v.len())
+}
+
+#[no_mangle]
+/// Pointer to the data behind `handle`, or null if unknown.
+pub extern "C" fn vec_get_ptr(handle: u64) -> *const uint32_t {
+    REGISTRY.lock().unwrap().get(&handle).map_or(ptr::null(), |v| v.as_ptr())
+}
+
+#[no_mangle]
+/// Free the vector behind `handle`. A missing or already-freed handle is
+/// a no-op lookup miss. The actual drop is deferred through `epoch`, so
+/// a reader that called `epoch::vec_pin` before reading `vec_get_ptr`'s
+/// result and hasn't called `vec_unpin` yet can't have it vanish out from
+/// under it -- an unpinned `vec_get_ptr` caller gets no such protection.
+pub extern "C" fn vec_free(handle: u64) {
+    // Bind first: `if let Some(vec) = REGISTRY.lock()...remove(..) { .. }`
+    // would extend the temporary's lifetime (and so the lock) across the
+    // whole body, serializing every other `vec_*` call behind `defer`.
+    let removed = REGISTRY.lock().unwrap().remove(&handle);
+    if let Some(vec) = removed {
+        epoch::defer(vec);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_free_is_a_no_op() {
+        let h = register(vec![1, 2, 3]).handle;
+        vec_free(h);
+        vec_free(h); // must not panic/double-free
+        assert_eq!(vec_len(h), 0);
+        assert!(vec_get_ptr(h).is_null());
+    }
+}
@@ -1,14 +1,62 @@
+//! `std` is the default feature. Built with `--no-default-features`,
+//! this crate is `#![no_std]` (`core` + `alloc` only): the worker pool,
+//! handle registry and epoch reclaimer all need threads and are cut out,
+//! leaving `fib_slow`, `fib_seq_results`, `drop_vec` and `VectorU32`
+//! available for bare-metal targets (see `no_std_support` for the demo
+//! allocator/panic handler backing that build). Add the optional
+//! `core_io` feature alongside it to route debug tracing through a
+//! `core_io::Write` sink.
+#![cfg_attr(not(feature = "std"), no_std)]
+// `libc::size_t`/`uint32_t` are deprecated in favor of `usize`/`u32`, but
+// this crate keeps them as the explicit, C-ABI-facing names at the FFI
+// boundary.
+#![allow(deprecated)]
+
+// `#![no_std]` builds get `core` injected automatically; under `std` it
+// needs to be named explicitly to resolve `core::slice`/`core::sync` from
+// sibling modules.
+#[cfg(feature = "std")]
+extern crate core;
 extern crate libc;
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(all(not(feature = "std"), feature = "core_io"))]
+extern crate core_io;
+
+#[cfg(feature = "std")]
+#[macro_use]
+extern crate lazy_static;
 
 use libc::{size_t, uint32_t};
-use std::slice;
-use std::mem;
+use core::slice;
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+mod fib;
+mod trace;
 
-use std::thread;
-use std::sync::{Arc, Mutex};
+#[cfg(not(feature = "std"))]
+mod no_std_support;
+
+#[cfg(feature = "std")]
+mod epoch;
+#[cfg(feature = "std")]
+mod handles;
+#[cfg(feature = "std")]
+mod pool;
+
+pub use fib::{fib_fast, set_fib_mode, FibMode};
+#[cfg(feature = "std")]
+pub use epoch::{defer_drop_vec, vec_pin, vec_unpin, Guard};
+#[cfg(feature = "std")]
+pub use handles::VectorHandle;
 
 #[repr(C)]
-/// Struct to pass Vec properties over ffi
+/// Struct to pass Vec properties over ffi directly, as a raw
+/// `data`/`len`/`cap` triple. Superseded by `VectorHandle` for the
+/// `fib_*_results` entry points; kept for callers that still manage
+/// ownership via `drop_vec` themselves.
 pub struct VectorU32 {
     data: *const uint32_t,
     len: size_t,
@@ -16,85 +64,115 @@ pub struct VectorU32 {
 }
 
 impl VectorU32 {
+    #[cfg_attr(feature = "std", allow(dead_code))]
     fn from_vec(vec: &Vec<u32>) -> VectorU32 {
         VectorU32 {data: vec.as_ptr(), len: vec.len(), cap: vec.capacity()}
     }
 }
 
 #[no_mangle]
-pub extern fn fib_slow(n: u32) -> u32 {
+/// Naive exponential recursion. Exists for benchmarking/demonstration
+/// against `fib_fast`/`fib_cached` (see `set_fib_mode`) -- every `fib_*`
+/// entry point below dispatches through `fib::fib` instead of calling
+/// this directly, so it only runs when a caller has opted into
+/// `FibMode::NaiveRecursive`. Saturates at `u32::MAX` like `fib_fast`,
+/// since it's directly reachable from FFI callers via that mode.
+pub extern "C" fn fib_slow(n: u32) -> u32 {
     if n == 0 || n == 1 { n }
-    else { fib_slow(n-1) + fib_slow(n-2) }
+    else { fib_slow(n-1).saturating_add(fib_slow(n-2)) }
 }
 
-//pub fn fib_fast(n: u32) -> u32 {
-//    let mut a = 1;
-//    let mut b = 1;
-//    let mut h;
-//    for _ in 0..n {
-//        h = a;
-//        a = b;
-//        b = h + b;
-//    }
-//    a
-//}
-
-/// Calculate fib. of list in separate threads
+#[cfg(feature = "std")]
+/// Calculate fib. of a list, spreading work across a fixed work-stealing
+/// worker pool (see `pool` module) instead of spawning one thread per
+/// element. Results line up positionally with `many`. Needs `std`
+/// (threads), so it's cut out of `--no-default-features` builds.
 pub fn fib_many(many: &[u32]) -> Vec<u32> {
-    let results = Arc::new(Mutex::new(Vec::with_capacity(many.len())));
-    let handles: Vec<_> = many.iter().cloned().map(|n| {
-        let results = results.clone();
-        thread::spawn(move || {
-            let res = fib_slow(n);
-            let mut results = results.lock().unwrap();
-            results.push(res);
-        })
-    }).collect();
-    for h in handles {
-        h.join().ok().expect("could not join");
-    }
-    let results = results.lock().unwrap().iter().cloned().collect::<Vec<_>>();
-    results
+    pool::map(many, pool::default_workers(), fib::fib)
+}
+
+#[cfg(feature = "std")]
+#[no_mangle]
+/// Calculate fib. sequentially from a list, return a handle to a new list
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `uint32_t`s.
+pub unsafe extern "C" fn fib_seq_results(data: *const uint32_t, length: size_t) -> VectorHandle {
+    let nums = slice::from_raw_parts(data, length);
+    let results = nums.iter().map(|&n| fib::fib(n)).collect::<Vec<_>>();
+    handles::register(results)
 }
 
+#[cfg(not(feature = "std"))]
 #[no_mangle]
-/// Calculate fib. sequentially from a list, return a new list
-pub extern fn fib_seq_results(data: *const uint32_t, length: size_t) -> VectorU32 {
-    let nums = unsafe { slice::from_raw_parts(data, length as usize) };
-    let results = nums.iter().map(|&n| fib_slow(n)).collect::<Vec<_>>();
+/// Calculate fib. sequentially from a list, return a new list. The
+/// handle registry (`VectorHandle`) needs `std::sync`/`std::collections`,
+/// so `--no-default-features` builds fall back to the raw-pointer
+/// `VectorU32` ABI here; the caller must still free it with `drop_vec`.
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `uint32_t`s.
+pub unsafe extern "C" fn fib_seq_results(data: *const uint32_t, length: size_t) -> VectorU32 {
+    let nums = slice::from_raw_parts(data, length);
+    let results = nums.iter().map(|&n| fib::fib(n)).collect::<Vec<_>>();
     let vec = VectorU32::from_vec(&results);
-    println!("[from-rust] create: {:?}", results.as_ptr());
-    println!("[from-rust] create: {:?}", results);
-    mem::forget(results);
+    core::mem::forget(results);
     vec
 }
 
+#[cfg(feature = "std")]
 #[no_mangle]
 /// Calculate fib. with threading from a list, return the count
-pub extern fn fib_threaded(data: *const uint32_t, length: size_t) -> uint32_t {
-    let nums = unsafe { slice::from_raw_parts(data, length as usize) };
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `uint32_t`s, and stay
+/// valid until this call returns.
+pub unsafe extern "C" fn fib_threaded(data: *const uint32_t, length: size_t) -> uint32_t {
+    // Pin: `data` is caller-owned and read concurrently by the worker
+    // pool, so it must not be reclaimed out from under us mid-call.
+    let _guard = epoch::pin();
+    let nums = slice::from_raw_parts(data, length);
     let results = fib_many(nums);
     results.len() as u32
 }
 
+#[cfg(feature = "std")]
 #[no_mangle]
-/// Calculate fib. with threading from a list, return a new list
-pub extern fn fib_threaded_results(data: *const uint32_t, length: size_t) -> VectorU32 {
-    let nums = unsafe { slice::from_raw_parts(data, length as usize) };
+/// Calculate fib. with threading from a list, return a handle to a new list
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `uint32_t`s, and stay
+/// valid until this call returns.
+pub unsafe extern "C" fn fib_threaded_results(data: *const uint32_t, length: size_t) -> VectorHandle {
+    let _guard = epoch::pin();
+    let nums = slice::from_raw_parts(data, length);
     let results = fib_many(nums);
-    let vec = VectorU32::from_vec(&results);
-    println!("[from-rust] create: {:?}", results.as_ptr());
-    println!("[from-rust] create: {:?}", results);
-    mem::forget(results);
-    vec
+    handles::register(results)
 }
 
 #[no_mangle]
 /// Drop a rust-made vec
-pub extern fn drop_vec(data_ptr: *mut uint32_t, len: size_t, cap: size_t) {
+///
+/// # Safety
+/// `data_ptr`/`len`/`cap` must be a `Vec<u32>`'s raw parts, not already freed.
+pub unsafe extern "C" fn drop_vec(data_ptr: *mut uint32_t, len: size_t, cap: size_t) {
     assert!(!data_ptr.is_null());
-    let vec = unsafe { Vec::from_raw_parts(data_ptr, len as usize, cap as usize) };
-    println!("[from-rust] drop:   {:?}", vec.as_ptr());
-    println!("[from-rust] drop:   {:?}", vec);
-    // mem::drop(vec);  // can also explicitly drop
+    let vec = Vec::from_raw_parts(data_ptr, len, cap);
+    trace::trace("[from-rust] drop");
+    drop(vec);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fib_many_preserves_input_order() {
+        // Shuffled, mixing small and large `n` so fast and slow workers
+        // finish out of order -- the output must still line up with `nums`.
+        let nums = vec![30, 1, 0, 25, 28, 2, 31, 3, 27, 26, 4, 29];
+        let expected = nums.iter().map(|&n| fib_slow(n)).collect::<Vec<_>>();
+        let actual = fib_many(&nums);
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,38 @@
+//! Demo `#[global_allocator]`/`#[panic_handler]` for `--no-default-features`
+//! builds, backed by `libc::malloc`/`free`. `cdylib`/`staticlib` are final
+//! linked artifacts and need both regardless of target, so without these
+//! `cargo build --no-default-features` can't actually produce one. Real
+//! bare-metal firmware embedding this crate as an `rlib` dependency instead
+//! should supply its own and drop this module -- a binary can only have one
+//! of each.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::panic::PanicInfo;
+
+struct LibcAllocator;
+
+unsafe impl GlobalAlloc for LibcAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // `malloc` only guarantees alignment suitable for any built-in
+        // type, not whatever `layout` asks for (e.g. `#[repr(align(32))]`
+        // types or SIMD); `posix_memalign` honors it exactly.
+        let align = layout.align().max(core::mem::size_of::<usize>());
+        let mut out: *mut libc::c_void = core::ptr::null_mut();
+        if libc::posix_memalign(&mut out, align, layout.size()) != 0 {
+            return core::ptr::null_mut();
+        }
+        out as *mut u8
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        libc::free(ptr as *mut libc::c_void)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LibcAllocator = LibcAllocator;
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,138 @@
+//! Fixed-size work-stealing worker pool used by `fib_many`.
+
+extern crate crossbeam_deque;
+extern crate num_cpus;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use self::crossbeam_deque::{Deque, Steal, Stealer};
+
+/// Tasks pulled from the injector into a worker's own deque at once.
+const REFILL_BATCH: usize = 4;
+
+/// One unit of work: compute `f(n)` and store it at `index`.
+struct Task {
+    index: usize,
+    n: u32,
+}
+
+/// Raw pointer to the output buffer; safe to share since each index is
+/// written exactly once.
+#[derive(Clone, Copy)]
+struct OutPtr(*mut u32);
+unsafe impl Send for OutPtr {}
+
+/// Number of workers to spawn when the caller doesn't care.
+pub fn default_workers() -> usize {
+    num_cpus::get()
+}
+
+/// Apply `f` to every element of `nums`, returning the results in the
+/// same order as the input. Work is spread across `n_workers` threads
+/// using a work-stealing deque rather than one thread per element.
+pub fn map<F>(nums: &[u32], n_workers: usize, f: F) -> Vec<u32>
+    where F: Fn(u32) -> u32 + Sync + Send + 'static
+{
+    let len = nums.len();
+    if len == 0 {
+        return Vec::new();
+    }
+    let n_workers = n_workers.max(1);
+    let f = Arc::new(f);
+
+    let mut out: Vec<u32> = vec![0u32; len];
+    let out_ptr = OutPtr(out.as_mut_ptr());
+
+    // `Deque` isn't `Sync`, only `Stealer` is -- keep it on this thread
+    // and hand out cloned stealers to the workers.
+    let injector = Deque::new();
+    for (index, &n) in nums.iter().enumerate() {
+        injector.push(Task { index, n });
+    }
+    let injector_stealer = injector.stealer();
+
+    let locals: Vec<Deque<Task>> = (0..n_workers).map(|_| Deque::new()).collect();
+    let stealers: Vec<Stealer<Task>> = locals.iter().map(|d| d.stealer()).collect();
+    let remaining = Arc::new(AtomicUsize::new(len));
+
+    let handles: Vec<_> = locals.into_iter().enumerate().map(|(id, local)| {
+        let injector_stealer = injector_stealer.clone();
+        let stealers = stealers.clone();
+        let remaining = remaining.clone();
+        let f = f.clone();
+        thread::spawn(move || worker_loop(id, local, injector_stealer, stealers, remaining, out_ptr, f))
+    }).collect();
+
+    for h in handles {
+        h.join().expect("fib worker panicked");
+    }
+
+    out
+}
+
+fn worker_loop<F>(
+    id: usize,
+    local: Deque<Task>,
+    injector: Stealer<Task>,
+    stealers: Vec<Stealer<Task>>,
+    remaining: Arc<AtomicUsize>,
+    out: OutPtr,
+    f: Arc<F>,
+) where F: Fn(u32) -> u32
+{
+    while remaining.load(Ordering::Acquire) > 0 {
+        let task = local.pop()
+            .or_else(|| { refill_from(&injector, &local); local.pop() })
+            .or_else(|| steal_from_siblings(id, &stealers));
+
+        let task = match task {
+            Some(t) => t,
+            None => {
+                thread::yield_now();
+                continue;
+            }
+        };
+
+        let result = f(task.n);
+        unsafe { *out.0.add(task.index) = result; }
+        remaining.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Pull up to `REFILL_BATCH` tasks from `stealer` onto `local`.
+fn refill_from(stealer: &Stealer<Task>, local: &Deque<Task>) {
+    for _ in 0..REFILL_BATCH {
+        match pop_retrying(|| stealer.steal()) {
+            Some(task) => local.push(task),
+            None => break,
+        }
+    }
+}
+
+/// Retry a `Steal` op until it's `Empty` or yields `Data`.
+fn pop_retrying<T, S>(mut steal: S) -> Option<T>
+    where S: FnMut() -> Steal<T>
+{
+    loop {
+        match steal() {
+            Steal::Empty => return None,
+            Steal::Data(t) => return Some(t),
+            Steal::Retry => continue,
+        }
+    }
+}
+
+/// Try every sibling's deque once; `None` if all were empty.
+fn steal_from_siblings(id: usize, stealers: &[Stealer<Task>]) -> Option<Task> {
+    for (i, stealer) in stealers.iter().enumerate() {
+        if i == id {
+            continue;
+        }
+        if let Some(task) = pop_retrying(|| stealer.steal()) {
+            return Some(task);
+        }
+    }
+    None
+}
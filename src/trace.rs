@@ -0,0 +1,36 @@
+//! Pluggable debug-trace sink for the FFI entry points.
+
+#[cfg(feature = "std")]
+pub fn trace(msg: &str) {
+    println!("{}", msg);
+}
+
+#[cfg(all(not(feature = "std"), feature = "core_io"))]
+mod no_std_sink {
+    use core_io::Write;
+
+    static mut SINK: Option<&'static mut Write> = None;
+
+    /// Register the sink debug traces are written to on this target
+    /// (e.g. a UART driver behind `core_io::Write`). Not synchronized --
+    /// call once during single-threaded startup.
+    pub unsafe fn set_sink(sink: &'static mut Write) {
+        SINK = Some(sink);
+    }
+
+    pub fn trace(msg: &str) {
+        unsafe {
+            if let Some(ref mut sink) = SINK {
+                let _ = sink.write_all(msg.as_bytes());
+                let _ = sink.write_all(b"\n");
+            }
+        }
+    }
+}
+
+#[cfg(all(not(feature = "std"), feature = "core_io"))]
+pub use self::no_std_sink::{set_sink, trace};
+
+/// No sink registered and no `core_io` feature enabled: trace as a no-op.
+#[cfg(all(not(feature = "std"), not(feature = "core_io")))]
+pub fn trace(_msg: &str) {}